@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::mir::{Function, Instr};
+use crate::{BlockId, Code, OpId};
+
+/// Control-flow analysis for a single [`Function`]: predecessors/successors
+/// derived from each block's terminator, a reverse-postorder block ordering,
+/// and an immediate-dominator tree. This is the shared foundation for
+/// `Code::verify`'s dominance check and later optimization/regalloc passes.
+pub struct Cfg {
+    entry: BlockId,
+    rpo: Vec<BlockId>,
+    rpo_number: HashMap<BlockId, usize>,
+    preds: HashMap<BlockId, Vec<BlockId>>,
+    succs: HashMap<BlockId, Vec<BlockId>>,
+    idom: HashMap<BlockId, BlockId>,
+}
+
+impl Cfg {
+    pub fn build(code: &Code, func: &Function) -> Cfg {
+        let entry = func.blocks[0];
+        let succs = Self::compute_successors(code, func);
+        let preds = Self::compute_predecessors(func, &succs);
+        let rpo = Self::reverse_postorder(entry, &succs);
+        let rpo_number: HashMap<BlockId, usize> = rpo.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+        let idom = Self::compute_idoms(entry, &rpo, &rpo_number, &preds);
+        Cfg { entry, rpo, rpo_number, preds, succs, idom }
+    }
+
+    pub fn entry(&self) -> BlockId {
+        self.entry
+    }
+
+    pub fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        self.preds.get(&block).map_or(&[], |v| &v[..])
+    }
+
+    pub fn successors(&self, block: BlockId) -> &[BlockId] {
+        self.succs.get(&block).map_or(&[], |v| &v[..])
+    }
+
+    /// Blocks in reverse-postorder. The entry block is always first.
+    pub fn reverse_postorder_blocks(&self) -> &[BlockId] {
+        &self.rpo
+    }
+
+    /// The immediate dominator of `block`, or `None` if `block` is
+    /// unreachable from the entry. The entry block is its own idom.
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idom.get(&block).copied()
+    }
+
+    /// Whether `a` dominates `b` (every path from the entry to `b` passes
+    /// through `a`). A block always dominates itself.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        let mut cur = b;
+        loop {
+            if cur == a {
+                return true;
+            }
+            let Some(next) = self.idom(cur) else { return false };
+            if next == cur {
+                return cur == a;
+            }
+            cur = next;
+        }
+    }
+
+    fn terminator<'a>(code: &'a Code, block_id: BlockId) -> Option<&'a Instr> {
+        let block = &code.blocks[block_id];
+        if block.ops.len() == 0 {
+            return None;
+        }
+        code.get_mir_instr(block_id, OpId::new(block.ops.len() - 1))
+    }
+
+    fn compute_successors(code: &Code, func: &Function) -> HashMap<BlockId, Vec<BlockId>> {
+        let mut succs = HashMap::new();
+        for &block_id in &func.blocks {
+            let mut out = Vec::new();
+            if let Some(term) = Self::terminator(code, block_id) {
+                match term {
+                    Instr::Br { target, .. } => out.push(*target),
+                    Instr::CondBr { true_bb, false_bb, .. } => {
+                        out.push(*true_bb);
+                        out.push(*false_bb);
+                    }
+                    _ => {}
+                }
+            }
+            succs.insert(block_id, out);
+        }
+        succs
+    }
+
+    fn compute_predecessors(func: &Function, succs: &HashMap<BlockId, Vec<BlockId>>) -> HashMap<BlockId, Vec<BlockId>> {
+        let mut preds: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for &block_id in &func.blocks {
+            preds.entry(block_id).or_default();
+        }
+        for &block_id in &func.blocks {
+            for &succ in succs.get(&block_id).map(|v| v.as_slice()).unwrap_or(&[]) {
+                preds.entry(succ).or_default().push(block_id);
+            }
+        }
+        preds
+    }
+
+    fn reverse_postorder(entry: BlockId, succs: &HashMap<BlockId, Vec<BlockId>>) -> Vec<BlockId> {
+        let mut visited = HashSet::new();
+        let mut postorder = Vec::new();
+        let mut stack = vec![(entry, false)];
+        while let Some((block_id, processed)) = stack.pop() {
+            if processed {
+                postorder.push(block_id);
+                continue;
+            }
+            if !visited.insert(block_id) {
+                continue;
+            }
+            stack.push((block_id, true));
+            for &succ in succs.get(&block_id).map(|v| v.as_slice()).unwrap_or(&[]).iter().rev() {
+                stack.push((succ, false));
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    /// Cooper-Harvey-Kennedy iterative dominator computation: number blocks
+    /// in reverse postorder, seed `idom(entry) = entry`, then repeatedly
+    /// recompute each other block's idom as the intersection (nearest common
+    /// dominator) of its already processed predecessors until nothing changes.
+    fn compute_idoms(
+        entry: BlockId,
+        rpo: &[BlockId],
+        rpo_number: &HashMap<BlockId, usize>,
+        preds: &HashMap<BlockId, Vec<BlockId>>,
+    ) -> HashMap<BlockId, BlockId> {
+        let mut idom = HashMap::new();
+        idom.insert(entry, entry);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in rpo.iter().skip(1) {
+                let mut new_idom = None;
+                for &p in preds.get(&block).map(|v| v.as_slice()).unwrap_or(&[]) {
+                    if !idom.contains_key(&p) {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(cur) => Self::intersect(cur, p, &idom, rpo_number),
+                    });
+                }
+                if let Some(new_idom) = new_idom {
+                    if idom.get(&block) != Some(&new_idom) {
+                        idom.insert(block, new_idom);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        idom
+    }
+
+    fn intersect(mut a: BlockId, mut b: BlockId, idom: &HashMap<BlockId, BlockId>, rpo_number: &HashMap<BlockId, usize>) -> BlockId {
+        while a != b {
+            while rpo_number[&a] > rpo_number[&b] {
+                a = idom[&a];
+            }
+            while rpo_number[&b] > rpo_number[&a] {
+                b = idom[&b];
+            }
+        }
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mir::Function;
+    use crate::ty::Type;
+
+    /// bb0 branches to bb1 and bb2, which both branch to bb3: a classic
+    /// diamond. Built directly against the idom machinery (`succs` and a
+    /// bare `Function`), bypassing `compute_successors`/`Cfg::build`, since
+    /// those need a real `Code` to read terminators out of and this tree has
+    /// no fixture for one.
+    fn diamond() -> (Function, HashMap<BlockId, Vec<BlockId>>) {
+        let (bb0, bb1, bb2, bb3) = (BlockId::new(0), BlockId::new(1), BlockId::new(2), BlockId::new(3));
+        let func = Function { name: None, ret_ty: Type::Bool, blocks: vec![bb0, bb1, bb2, bb3] };
+        let succs = HashMap::from([(bb0, vec![bb1, bb2]), (bb1, vec![bb3]), (bb2, vec![bb3]), (bb3, vec![])]);
+        (func, succs)
+    }
+
+    #[test]
+    fn diamond_merge_block_is_immediately_dominated_by_entry() {
+        let (func, succs) = diamond();
+        let entry = func.blocks[0];
+        let rpo = Cfg::reverse_postorder(entry, &succs);
+        let rpo_number: HashMap<BlockId, usize> = rpo.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+        let preds = Cfg::compute_predecessors(&func, &succs);
+        let idom = Cfg::compute_idoms(entry, &rpo, &rpo_number, &preds);
+
+        let (bb1, bb2, bb3) = (BlockId::new(1), BlockId::new(2), BlockId::new(3));
+        assert_eq!(idom[&bb1], entry);
+        assert_eq!(idom[&bb2], entry);
+        // bb3 is reachable via both arms of the diamond, so neither bb1 nor
+        // bb2 alone dominates it: its immediate dominator is the branch point
+        // back up at the entry.
+        assert_eq!(idom[&bb3], entry);
+    }
+
+    #[test]
+    fn reverse_postorder_puts_entry_first_and_respects_edges() {
+        let (func, succs) = diamond();
+        let entry = func.blocks[0];
+        let rpo = Cfg::reverse_postorder(entry, &succs);
+        assert_eq!(rpo[0], entry);
+        assert_eq!(rpo.len(), 4);
+        // The diamond has no back edges, so every edge's source comes first.
+        let pos = |b: BlockId| rpo.iter().position(|&x| x == b).unwrap();
+        for (&from, tos) in &succs {
+            for &to in tos {
+                assert!(pos(from) < pos(to), "{from:?} -> {to:?} goes backwards in RPO");
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::mir::{BlockState, Const, Function, Instr};
+use crate::{BlockId, Code, OpId};
+
+impl Code {
+    /// Emits a Graphviz DOT rendering of `func`'s control-flow graph, in the
+    /// spirit of rustc's `mir/graphviz.rs`: one node per block listing its
+    /// instructions, and edges derived from each block's terminator (a
+    /// single edge for `Br`, `true`/`false` edges for `CondBr`, no out-edge
+    /// for `Ret`). The entry block is marked, and nodes are colored by their
+    /// `BlockState`.
+    pub fn write_dot(&self, func: &Function, w: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(w, "digraph Mir {{")?;
+        writeln!(w, "    node [shape=box, fontname=\"monospace\"];")?;
+
+        let entry = func.blocks[0];
+        for &block_id in &func.blocks {
+            self.write_node(w, block_id, block_id == entry)?;
+        }
+        for &block_id in &func.blocks {
+            self.write_edges(w, block_id)?;
+        }
+        writeln!(w, "}}")
+    }
+
+    fn write_node(&self, w: &mut impl fmt::Write, block_id: BlockId, is_entry: bool) -> fmt::Result {
+        let block = &self.blocks[block_id];
+        let mut label = String::new();
+        if is_entry {
+            label.push_str("(entry) ");
+        }
+        label.push_str(&format!("bb{}:\\l", block_id.index()));
+        for &(param, ref ty) in self.mir_code.block_params(block_id) {
+            label.push_str(&format!("  %{} : {:?}\\l", param.index(), ty));
+        }
+        for i in 0..block.ops.len() {
+            if let Some(instr_id) = block.ops[OpId::new(i)].as_mir_instr() {
+                label.push_str(&format!("  %{} = {}\\l", instr_id.index(), self.fmt_instr(&self.mir_code.instrs[instr_id])));
+            }
+        }
+
+        let color = match self.mir_code.block_state(block_id) {
+            None | Some(BlockState::Created) => "lightgray",
+            Some(BlockState::Started) => "lightyellow",
+            Some(BlockState::Ended) => "white",
+        };
+        writeln!(
+            w,
+            "    bb{} [label=\"{}\", style=filled, fillcolor={}];",
+            block_id.index(),
+            label.replace('"', "\\\""),
+            color,
+        )
+    }
+
+    fn write_edges(&self, w: &mut impl fmt::Write, block_id: BlockId) -> fmt::Result {
+        let block = &self.blocks[block_id];
+        if block.ops.len() == 0 {
+            return Ok(());
+        }
+        let Some(term) = self.get_mir_instr(block_id, OpId::new(block.ops.len() - 1)) else { return Ok(()) };
+        match term {
+            Instr::Br { target, .. } => {
+                writeln!(w, "    bb{} -> bb{};", block_id.index(), target.index())
+            }
+            Instr::CondBr { true_bb, false_bb, .. } => {
+                writeln!(w, "    bb{} -> bb{} [label=\"true\"];", block_id.index(), true_bb.index())?;
+                writeln!(w, "    bb{} -> bb{} [label=\"false\"];", block_id.index(), false_bb.index())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn fmt_instr(&self, instr: &Instr) -> String {
+        match instr {
+            Instr::Void => "Void".to_string(),
+            Instr::Const(c) => self.fmt_const(c),
+            Instr::Alloca(ty) => format!("Alloca {:?}", ty),
+            Instr::LogicalNot(v) => format!("Not %{}", v.index()),
+            Instr::Call { arguments, func } => format!("Call f{}({})", func.index(), fmt_args(arguments)),
+            Instr::Intrinsic { arguments, intr, .. } => format!("{:?}({})", intr, fmt_args(arguments)),
+            Instr::Reinterpret(v, ty) => format!("Reinterpret %{} as {:?}", v.index(), ty),
+            Instr::Truncate(v, ty) => format!("Truncate %{} as {:?}", v.index(), ty),
+            Instr::SignExtend(v, ty) => format!("SignExtend %{} as {:?}", v.index(), ty),
+            Instr::ZeroExtend(v, ty) => format!("ZeroExtend %{} as {:?}", v.index(), ty),
+            Instr::FloatCast(v, ty) => format!("FloatCast %{} as {:?}", v.index(), ty),
+            Instr::FloatToInt(v, ty) => format!("FloatToInt %{} as {:?}", v.index(), ty),
+            Instr::IntToFloat(v, ty) => format!("IntToFloat %{} as {:?}", v.index(), ty),
+            Instr::Load(v) => format!("Load %{}", v.index()),
+            Instr::Store { location, value } => format!("Store %{}, %{}", location.index(), value.index()),
+            Instr::AddressOfStatic(id) => format!("AddressOfStatic s{}", id.index()),
+            Instr::Pointer { op, is_mut } => format!("Pointer {}%{}", if *is_mut { "mut " } else { "" }, op.index()),
+            Instr::Struct { fields, id } => format!("Struct#{}({})", id.index(), fmt_args(fields)),
+            Instr::StructLit { fields, id } => format!("StructLit#{}({})", id.index(), fmt_args(fields)),
+            Instr::DirectFieldAccess { val, index } => format!("DirectFieldAccess %{}.{}", val.index(), index),
+            Instr::IndirectFieldAccess { val, index } => format!("IndirectFieldAccess %{}.{}", val.index(), index),
+            Instr::Ret(v) => format!("Ret %{}", v.index()),
+            Instr::Br { target, args } => format!("Br bb{}({})", target.index(), fmt_args(args)),
+            Instr::CondBr { condition, true_bb, true_args, false_bb, false_args } => format!(
+                "CondBr %{} ? bb{}({}) : bb{}({})",
+                condition.index(),
+                true_bb.index(),
+                fmt_args(true_args),
+                false_bb.index(),
+                fmt_args(false_args),
+            ),
+        }
+    }
+
+    fn fmt_const(&self, c: &Const) -> String {
+        match c {
+            Const::Int { lit, .. } => lit.to_string(),
+            Const::Float { lit, .. } => lit.to_string(),
+            Const::Str { id, .. } => format!("{:?}", self.mir_code.strings[*id].to_string_lossy()),
+            Const::Bool(b) => b.to_string(),
+            Const::Ty(ty) => format!("{:?}", ty),
+            Const::Mod(id) => format!("mod#{}", id.index()),
+            Const::StructLit { id, .. } => format!("StructLit#{}", id.index()),
+        }
+    }
+
+}
+
+/// Formats a list of operand ids as `%a, %b, %c`. Free-standing (unlike
+/// `fmt_instr`/`fmt_const`) since it needs no access to `self`'s interned
+/// strings or struct layouts, just the ids themselves.
+fn fmt_args(args: &[crate::mir::InstrId]) -> String {
+    args.iter().map(|a| format!("%{}", a.index())).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mir::InstrId;
+
+    #[test]
+    fn fmt_args_joins_operands_with_percent_prefixes() {
+        let args = [InstrId::new(0), InstrId::new(2), InstrId::new(5)];
+        assert_eq!(fmt_args(&args), "%0, %2, %5");
+    }
+
+    #[test]
+    fn fmt_args_of_an_empty_slice_is_empty() {
+        assert_eq!(fmt_args(&[]), "");
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::{BTreeMap, HashMap};
+
+use index_vec::{IndexVec, define_index_type};
+use thiserror::Error;
+
+use crate::hir::Intrinsic;
+use crate::mir::{Const, Function, Instr, InstrId, MirCode, StaticId, Struct, StructId};
+use crate::ty::Type;
+use crate::{BlockId, Code, OpId};
+
+define_index_type!(pub struct AllocId = u32;);
+
+/// One byte-addressable block of memory, as produced by an `Alloca` or a
+/// static initializer. `bytes`/`initialized` hold the concrete
+/// representation; `relocations` records which byte offsets hold a pointer
+/// into another `Allocation` rather than plain data, in the spirit of
+/// rustc's `mir::interpret::Allocation`.
+#[derive(Clone, Debug)]
+pub struct Allocation {
+    bytes: Vec<u8>,
+    initialized: Vec<bool>,
+    relocations: BTreeMap<usize, AllocId>,
+    ty: Type,
+}
+
+impl Allocation {
+    fn new(ty: Type, size: usize) -> Self {
+        Allocation { bytes: vec![0; size], initialized: vec![false; size], relocations: BTreeMap::new(), ty }
+    }
+
+    fn write_bytes(&mut self, offset: usize, data: &[u8]) -> Result<(), EvalError> {
+        let end = offset.checked_add(data.len()).ok_or(EvalError::OutOfBounds)?;
+        if end > self.bytes.len() {
+            return Err(EvalError::OutOfBounds);
+        }
+        self.bytes[offset..end].copy_from_slice(data);
+        for slot in &mut self.initialized[offset..end] {
+            *slot = true;
+        }
+        self.relocations.retain(|&reloc_off, _| reloc_off < offset || reloc_off >= end);
+        Ok(())
+    }
+
+    fn read_bytes(&self, offset: usize, len: usize) -> Result<&[u8], EvalError> {
+        let end = offset.checked_add(len).ok_or(EvalError::OutOfBounds)?;
+        if end > self.bytes.len() {
+            return Err(EvalError::OutOfBounds);
+        }
+        if self.initialized[offset..end].iter().any(|&b| !b) {
+            return Err(EvalError::ReadUninitialized);
+        }
+        Ok(&self.bytes[offset..end])
+    }
+
+    fn set_relocation(&mut self, offset: usize, target: AllocId) {
+        self.relocations.insert(offset, target);
+    }
+
+    fn relocation_at(&self, offset: usize) -> Option<AllocId> {
+        self.relocations.get(&offset).copied()
+    }
+}
+
+/// A pointer-like value: an allocation plus a byte offset into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pointer {
+    pub alloc: AllocId,
+    pub offset: usize,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EvalError {
+    #[error("instruction {0:?} is not a constant input and cannot be const-evaluated")]
+    NotConstant(InstrId),
+    #[error("access out of bounds of its allocation")]
+    OutOfBounds,
+    #[error("read from uninitialized bytes")]
+    ReadUninitialized,
+    #[error("load at {0:?} is misaligned for its type")]
+    MisalignedAccess(Pointer),
+    #[error("{0:?} is not a pointer")]
+    NotAPointer(InstrId),
+    #[error("struct {0:?} has no known layout")]
+    UnknownStruct(StructId),
+    #[error("intrinsic {0:?} is not supported in const-eval")]
+    UnsupportedIntrinsic(Intrinsic),
+    #[error("function did not end in a Ret")]
+    NoReturn,
+    #[error("field index {0} out of range")]
+    BadFieldIndex(usize),
+    #[error("calls are not foldable at const-eval time")]
+    UnsupportedCall,
+    #[error("expected a numeric constant, found {0:?}")]
+    NotNumeric(Const),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("intrinsic {intr:?} expects {expected} argument(s), found {found}")]
+    IntrinsicArityMismatch { intr: Intrinsic, expected: usize, found: usize },
+}
+
+/// The interpreter's notion of a runtime value: either a first-class
+/// `Const` (ints, floats, bools, aggregates, ...) or a pointer into one of
+/// this evaluation's allocations.
+#[derive(Clone, Debug, PartialEq)]
+enum EvalValue {
+    Const(Const),
+    Ptr(Pointer),
+}
+
+/// Executes a [`Function`] whose inputs are all [`Const`]s, returning the
+/// resulting `Const` or a typed error. Modeled on rustc's `mir::interpret`:
+/// memory lives in byte-addressable [`Allocation`]s, and pointers are
+/// tracked symbolically via relocations rather than real addresses.
+pub struct Interp<'a> {
+    code: &'a Code,
+    allocs: IndexVec<AllocId, Allocation>,
+    /// One allocation per `StaticId`, created lazily on first
+    /// `AddressOfStatic` and reused after that so two addresses of the same
+    /// static see each other's writes.
+    static_allocs: HashMap<StaticId, AllocId>,
+}
+
+impl<'a> Interp<'a> {
+    pub fn new(code: &'a Code) -> Self {
+        Interp { code, allocs: IndexVec::new(), static_allocs: HashMap::new() }
+    }
+
+    /// Borrows through `self.code` rather than `self`, so the result can
+    /// outlive a `&self`/`&mut self` call taken alongside it (e.g. reading
+    /// an `Instr` here while also passing `&mut self` to `eval_instr`).
+    fn mir(&self) -> &'a MirCode {
+        &self.code.mir_code
+    }
+
+    /// Executes `func` with `args` bound to the entry block's params
+    /// (see `mir::Value::Param`), following `Br`/`CondBr` by binding each
+    /// destination block's params to the branch's resolved `args`.
+    pub fn eval_function(&mut self, func: &Function, args: &[Const]) -> Result<Const, EvalError> {
+        let mut locals: HashMap<InstrId, EvalValue> = HashMap::new();
+        let mut block_id = func.blocks[0];
+        let mut incoming = args.to_vec();
+
+        loop {
+            let params = self.mir().block_params(block_id);
+            assert_eq!(params.len(), incoming.len(), "block param/arg arity is checked by Code::verify");
+            for (&(param_instr, _), value) in params.iter().zip(incoming.drain(..)) {
+                locals.insert(param_instr, EvalValue::Const(value));
+            }
+            let block = &self.code.blocks[block_id];
+            let num_ops = block.ops.len();
+
+            let mut next: Option<(BlockId, Vec<Const>)> = None;
+            for i in 0..num_ops {
+                let op = OpId::new(i);
+                let Some(instr_id) = block.ops[op].as_mir_instr() else { continue };
+                let instr = &self.mir().instrs[instr_id];
+                match instr {
+                    Instr::Void => {}
+                    Instr::Ret(v) => return self.as_const(&locals, *v),
+                    Instr::Br { target, args } => {
+                        next = Some((*target, self.resolve_args(&locals, args)?));
+                        break;
+                    }
+                    Instr::CondBr { condition, true_bb, true_args, false_bb, false_args } => {
+                        let cond = self.as_bool(&locals, *condition)?;
+                        let (target, args) = if cond { (*true_bb, true_args) } else { (*false_bb, false_args) };
+                        next = Some((target, self.resolve_args(&locals, args)?));
+                        break;
+                    }
+                    other => {
+                        let value = self.eval_instr(&locals, other)?;
+                        locals.insert(instr_id, value);
+                    }
+                }
+            }
+
+            let Some((target, resolved)) = next else { return Err(EvalError::NoReturn) };
+            block_id = target;
+            incoming = resolved;
+        }
+    }
+
+    fn resolve_args(&self, locals: &HashMap<InstrId, EvalValue>, args: &[InstrId]) -> Result<Vec<Const>, EvalError> {
+        args.iter().map(|&a| self.as_const(locals, a)).collect()
+    }
+
+    fn as_const(&self, locals: &HashMap<InstrId, EvalValue>, id: InstrId) -> Result<Const, EvalError> {
+        match locals.get(&id) {
+            Some(EvalValue::Const(c)) => Ok(c.clone()),
+            _ => Err(EvalError::NotConstant(id)),
+        }
+    }
+
+    fn as_bool(&self, locals: &HashMap<InstrId, EvalValue>, id: InstrId) -> Result<bool, EvalError> {
+        match self.as_const(locals, id)? {
+            Const::Bool(b) => Ok(b),
+            _ => Err(EvalError::NotConstant(id)),
+        }
+    }
+
+    fn as_ptr(&self, locals: &HashMap<InstrId, EvalValue>, id: InstrId) -> Result<Pointer, EvalError> {
+        match locals.get(&id) {
+            Some(EvalValue::Ptr(p)) => Ok(*p),
+            _ => Err(EvalError::NotAPointer(id)),
+        }
+    }
+
+    fn eval_instr(&mut self, locals: &HashMap<InstrId, EvalValue>, instr: &Instr) -> Result<EvalValue, EvalError> {
+        Ok(match instr {
+            Instr::Const(c) => EvalValue::Const(c.clone()),
+            Instr::Alloca(ty) => {
+                let (size, _align) = layout_of(ty, &self.mir().structs);
+                let alloc = self.allocs.push(Allocation::new(ty.clone(), size));
+                EvalValue::Ptr(Pointer { alloc, offset: 0 })
+            }
+            Instr::AddressOfStatic(id) => {
+                let alloc_id = match self.static_allocs.get(id) {
+                    Some(&alloc_id) => alloc_id,
+                    None => {
+                        let value = &self.mir().statics[*id];
+                        let (size, _align) = layout_of(&value.ty(), &self.mir().structs);
+                        let mut alloc = Allocation::new(value.ty(), size);
+                        Self::write_const(&mut alloc, 0, value)?;
+                        let alloc_id = self.allocs.push(alloc);
+                        self.static_allocs.insert(*id, alloc_id);
+                        alloc_id
+                    }
+                };
+                EvalValue::Ptr(Pointer { alloc: alloc_id, offset: 0 })
+            }
+            Instr::Load(ptr) => {
+                let p = self.as_ptr(locals, *ptr)?;
+                match self.allocs[p.alloc].relocation_at(p.offset) {
+                    Some(target) => EvalValue::Ptr(Pointer { alloc: target, offset: 0 }),
+                    None => {
+                        let ty = self.allocs[p.alloc].ty.clone();
+                        EvalValue::Const(self.read_const(p, &ty)?)
+                    }
+                }
+            }
+            Instr::Store { location, value } => {
+                let p = self.as_ptr(locals, *location)?;
+                match locals.get(value) {
+                    Some(&EvalValue::Ptr(target)) => {
+                        let alloc = &mut self.allocs[p.alloc];
+                        // The bytes themselves are never read back (a relocated
+                        // offset is resolved by `relocation_at` before the raw
+                        // bytes would matter); this just claims the pointer's
+                        // width so a later non-pointer write at the same offset
+                        // clears the relocation via `write_bytes`.
+                        alloc.write_bytes(p.offset, &[0u8; 8])?;
+                        alloc.set_relocation(p.offset, target.alloc);
+                    }
+                    _ => {
+                        let c = self.as_const(locals, *value)?;
+                        let ty = self.allocs[p.alloc].ty.clone();
+                        let alloc = &mut self.allocs[p.alloc];
+                        Self::write_bytes_for(alloc, p.offset, &c, &ty)?;
+                    }
+                }
+                EvalValue::Const(Const::Bool(true)) // Store has no meaningful result; see `Instr::Void` convention
+            }
+            Instr::DirectFieldAccess { val, index } => {
+                match self.as_const(locals, *val)? {
+                    Const::StructLit { mut fields, .. } => {
+                        if *index >= fields.len() {
+                            return Err(EvalError::BadFieldIndex(*index));
+                        }
+                        EvalValue::Const(fields.swap_remove(*index))
+                    }
+                    _ => return Err(EvalError::NotConstant(*val)),
+                }
+            }
+            Instr::IndirectFieldAccess { val, index } => {
+                let p = self.as_ptr(locals, *val)?;
+                let ty = self.allocs[p.alloc].ty.clone();
+                let Type::Struct(id) = ty else { return Err(EvalError::NotAPointer(*val)) };
+                let Some(strukt) = self.mir().structs.get(&id) else { return Err(EvalError::UnknownStruct(id)) };
+                let Some(&field_off) = strukt.layout.field_offsets.get(*index) else {
+                    return Err(EvalError::BadFieldIndex(*index));
+                };
+                let Some(field_ty) = strukt.field_tys.get(*index).cloned() else {
+                    return Err(EvalError::BadFieldIndex(*index));
+                };
+                let field_ptr = Pointer { alloc: p.alloc, offset: p.offset + field_off };
+                if let Some(target) = self.allocs[p.alloc].relocation_at(field_ptr.offset) {
+                    return Ok(EvalValue::Ptr(Pointer { alloc: target, offset: 0 }));
+                }
+                EvalValue::Const(self.read_const(field_ptr, &field_ty)?)
+            }
+            Instr::Struct { fields, id } | Instr::StructLit { fields, id } => {
+                let values = fields.iter().map(|&f| self.as_const(locals, f)).collect::<Result<Vec<_>, _>>()?;
+                EvalValue::Const(Const::StructLit { fields: values, id: *id })
+            }
+            Instr::LogicalNot(v) => match self.as_const(locals, *v)? {
+                Const::Bool(b) => EvalValue::Const(Const::Bool(!b)),
+                _ => return Err(EvalError::NotConstant(*v)),
+            },
+            Instr::Reinterpret(v, ty) => EvalValue::Const(reinterpret(self.as_const(locals, *v)?, ty)?),
+            Instr::Truncate(v, ty) => EvalValue::Const(truncate(self.as_const(locals, *v)?, ty)?),
+            Instr::SignExtend(v, ty) => EvalValue::Const(sign_extend(self.as_const(locals, *v)?, ty)?),
+            Instr::ZeroExtend(v, ty) => EvalValue::Const(zero_extend(self.as_const(locals, *v)?, ty)?),
+            Instr::FloatCast(v, ty) => EvalValue::Const(cast_float(self.as_const(locals, *v)?, ty)?),
+            Instr::FloatToInt(v, ty) => EvalValue::Const(float_to_int(self.as_const(locals, *v)?, ty)?),
+            Instr::IntToFloat(v, ty) => EvalValue::Const(int_to_float(self.as_const(locals, *v)?, ty)?),
+            Instr::Intrinsic { arguments, ty, intr } => {
+                let args = arguments.iter().map(|&a| self.as_const(locals, a)).collect::<Result<Vec<_>, _>>()?;
+                EvalValue::Const(eval_intrinsic(*intr, &args, ty)?)
+            }
+            Instr::Pointer { op, .. } => EvalValue::Ptr(self.as_ptr(locals, *op)?),
+            Instr::Call { .. } => return Err(EvalError::UnsupportedCall),
+            Instr::Void | Instr::Ret(_) | Instr::Br { .. } | Instr::CondBr { .. } => unreachable!("terminators handled by the caller"),
+        })
+    }
+
+    fn write_const(alloc: &mut Allocation, offset: usize, value: &Const) -> Result<(), EvalError> {
+        let ty = value.ty();
+        Self::write_bytes_for(alloc, offset, value, &ty)
+    }
+
+    fn write_bytes_for(alloc: &mut Allocation, offset: usize, value: &Const, ty: &Type) -> Result<(), EvalError> {
+        let bytes = const_to_bytes(value, ty);
+        alloc.write_bytes(offset, &bytes)
+    }
+
+    /// Reads `ty`-typed plain data back out of `ptr`. Callers check
+    /// `relocation_at` first: a relocated offset holds a pointer, whose
+    /// bytes aren't meaningful data to decode this way.
+    fn read_const(&self, ptr: Pointer, ty: &Type) -> Result<Const, EvalError> {
+        let (size, align) = layout_of(ty, &self.mir().structs);
+        if ptr.offset % align.max(1) != 0 {
+            return Err(EvalError::MisalignedAccess(ptr));
+        }
+        let alloc = &self.allocs[ptr.alloc];
+        let bytes = alloc.read_bytes(ptr.offset, size)?;
+        Ok(bytes_to_const(bytes, ty))
+    }
+}
+
+/// The bit width of an integer `Type`, or `None` for anything that isn't
+/// one (`Bool`, a float type, a struct, ...).
+fn int_bit_width(ty: &Type) -> Option<u32> {
+    match ty {
+        Type::I8 | Type::U8 => Some(8),
+        Type::I16 | Type::U16 => Some(16),
+        Type::I32 | Type::U32 => Some(32),
+        Type::I64 | Type::U64 => Some(64),
+        _ => None,
+    }
+}
+
+fn width_mask(width: u32) -> u64 {
+    if width >= 64 { u64::MAX } else { (1u64 << width) - 1 }
+}
+
+/// Best-effort size/alignment for a `Type`, in bytes. Struct layouts are
+/// authoritative (`StructLayout`); `Bool` and the int/float types report
+/// their real width; everything else is treated as a plain 8-byte scalar.
+fn layout_of(ty: &Type, structs: &HashMap<StructId, Struct>) -> (usize, usize) {
+    match ty {
+        Type::Bool | Type::I8 | Type::U8 => (1, 1),
+        Type::I16 | Type::U16 => (2, 2),
+        Type::I32 | Type::U32 | Type::F32 => (4, 4),
+        Type::I64 | Type::U64 | Type::F64 => (8, 8),
+        Type::Struct(id) => structs.get(id).map_or((0, 1), |s| (s.layout.size, s.layout.alignment)),
+        _ => (8, 8),
+    }
+}
+
+fn const_to_bytes(value: &Const, ty: &Type) -> Vec<u8> {
+    match value {
+        Const::Int { lit, .. } => {
+            let width = int_bit_width(ty).map_or(8, |bits| bits as usize / 8);
+            lit.to_le_bytes()[..width].to_vec()
+        }
+        Const::Float { lit, .. } if *ty == Type::F32 => (*lit as f32).to_le_bytes().to_vec(),
+        Const::Float { lit, .. } => lit.to_le_bytes().to_vec(),
+        Const::Bool(b) => vec![*b as u8],
+        _ => Vec::new(),
+    }
+}
+
+fn bytes_to_const(bytes: &[u8], ty: &Type) -> Const {
+    match ty {
+        Type::Bool => Const::Bool(bytes.first().copied().unwrap_or(0) != 0),
+        Type::F32 => {
+            let mut buf = [0u8; 4];
+            buf[..bytes.len().min(4)].copy_from_slice(&bytes[..bytes.len().min(4)]);
+            Const::Float { lit: f32::from_le_bytes(buf) as f64, ty: ty.clone() }
+        }
+        Type::F64 => {
+            let mut buf = [0u8; 8];
+            buf[..bytes.len().min(8)].copy_from_slice(&bytes[..bytes.len().min(8)]);
+            Const::Float { lit: f64::from_le_bytes(buf), ty: ty.clone() }
+        }
+        other => {
+            let mut buf = [0u8; 8];
+            buf[..bytes.len().min(8)].copy_from_slice(&bytes[..bytes.len().min(8)]);
+            Const::Int { lit: u64::from_le_bytes(buf), ty: other.clone() }
+        }
+    }
+}
+
+fn as_int(c: Const) -> Result<u64, EvalError> {
+    match c {
+        Const::Int { lit, .. } => Ok(lit),
+        Const::Bool(b) => Ok(b as u64),
+        other => Err(EvalError::NotNumeric(other)),
+    }
+}
+
+fn as_float(c: Const) -> Result<f64, EvalError> {
+    match c {
+        Const::Float { lit, .. } => Ok(lit),
+        other => Err(EvalError::NotNumeric(other)),
+    }
+}
+
+/// A bitcast: keeps the operand's exact bit pattern and only retags its
+/// `Type`, unlike the width-changing casts below.
+fn reinterpret(c: Const, ty: &Type) -> Result<Const, EvalError> {
+    Ok(Const::Int { lit: as_int(c)?, ty: ty.clone() })
+}
+
+/// Narrows an integer to `ty`'s width by masking off the high bits.
+fn truncate(c: Const, ty: &Type) -> Result<Const, EvalError> {
+    let width = int_bit_width(ty).unwrap_or(64);
+    Ok(Const::Int { lit: as_int(c)? & width_mask(width), ty: ty.clone() })
+}
+
+/// Widens an integer to `ty`'s width, replicating the operand's sign bit
+/// into the new high bits.
+fn sign_extend(c: Const, ty: &Type) -> Result<Const, EvalError> {
+    let src_width = int_bit_width(&c.ty()).unwrap_or(64);
+    let masked = as_int(c)? & width_mask(src_width);
+    let sign_bit = 1u64 << src_width.saturating_sub(1).min(63);
+    let lit = if src_width < 64 && masked & sign_bit != 0 { masked | !width_mask(src_width) } else { masked };
+    let dst_width = int_bit_width(ty).unwrap_or(64);
+    Ok(Const::Int { lit: lit & width_mask(dst_width), ty: ty.clone() })
+}
+
+/// Widens an integer to `ty`'s width, filling the new high bits with zero.
+fn zero_extend(c: Const, ty: &Type) -> Result<Const, EvalError> {
+    let src_width = int_bit_width(&c.ty()).unwrap_or(64);
+    Ok(Const::Int { lit: as_int(c)? & width_mask(src_width), ty: ty.clone() })
+}
+
+fn cast_float(c: Const, ty: &Type) -> Result<Const, EvalError> {
+    Ok(Const::Float { lit: as_float(c)?, ty: ty.clone() })
+}
+
+fn float_to_int(c: Const, ty: &Type) -> Result<Const, EvalError> {
+    Ok(Const::Int { lit: as_float(c)? as u64, ty: ty.clone() })
+}
+
+fn int_to_float(c: Const, ty: &Type) -> Result<Const, EvalError> {
+    Ok(Const::Float { lit: as_int(c)? as f64, ty: ty.clone() })
+}
+
+/// Evaluates `Add`, `Sub`, `Mul` and `Div` (wrapping for integers) and
+/// `Eq`. Every other intrinsic, including the other comparisons and the
+/// bitwise ones, reports `UnsupportedIntrinsic`.
+fn eval_intrinsic(intr: Intrinsic, args: &[Const], ty: &Type) -> Result<Const, EvalError> {
+    let is_float = args.iter().any(|a| matches!(a, Const::Float { .. }));
+    let int_args = || -> Result<Vec<u64>, EvalError> { args.iter().cloned().map(as_int).collect() };
+    let float_args = || -> Result<Vec<f64>, EvalError> { args.iter().cloned().map(as_float).collect() };
+    let require_binary = || -> Result<(), EvalError> {
+        if args.len() == 2 {
+            Ok(())
+        } else {
+            Err(EvalError::IntrinsicArityMismatch { intr, expected: 2, found: args.len() })
+        }
+    };
+
+    use Intrinsic::*;
+    match intr {
+        Add if is_float => Ok(Const::Float { lit: sum(float_args()?), ty: ty.clone() }),
+        Add => Ok(Const::Int { lit: int_args()?.into_iter().fold(0u64, u64::wrapping_add), ty: ty.clone() }),
+        Sub if is_float => {
+            require_binary()?;
+            let a = float_args()?;
+            Ok(Const::Float { lit: a[0] - a[1], ty: ty.clone() })
+        }
+        Sub => {
+            require_binary()?;
+            let a = int_args()?;
+            Ok(Const::Int { lit: a[0].wrapping_sub(a[1]), ty: ty.clone() })
+        }
+        Mul if is_float => {
+            require_binary()?;
+            let a = float_args()?;
+            Ok(Const::Float { lit: a[0] * a[1], ty: ty.clone() })
+        }
+        Mul => {
+            require_binary()?;
+            let a = int_args()?;
+            Ok(Const::Int { lit: a[0].wrapping_mul(a[1]), ty: ty.clone() })
+        }
+        Div if is_float => {
+            require_binary()?;
+            let a = float_args()?;
+            Ok(Const::Float { lit: a[0] / a[1], ty: ty.clone() })
+        }
+        Div => {
+            require_binary()?;
+            let a = int_args()?;
+            Ok(Const::Int { lit: a[0].checked_div(a[1]).ok_or(EvalError::DivisionByZero)?, ty: ty.clone() })
+        }
+        Eq => {
+            require_binary()?;
+            Ok(Const::Bool(args[0] == args[1]))
+        }
+        _ => Err(EvalError::UnsupportedIntrinsic(intr)),
+    }
+}
+
+fn sum(vals: Vec<f64>) -> f64 {
+    vals.into_iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_records_and_resolves_relocations() {
+        let mut alloc = Allocation::new(Type::Bool, 8);
+        assert_eq!(alloc.relocation_at(0), None);
+        let target = AllocId::new(3);
+        alloc.set_relocation(0, target);
+        assert_eq!(alloc.relocation_at(0), Some(target));
+    }
+
+    #[test]
+    fn allocation_clears_relocation_on_overwrite() {
+        let mut alloc = Allocation::new(Type::Bool, 8);
+        alloc.set_relocation(0, AllocId::new(1));
+        alloc.write_bytes(0, &[1]).unwrap();
+        assert_eq!(alloc.relocation_at(0), None);
+    }
+
+    #[test]
+    fn eval_intrinsic_sub_rejects_wrong_arity() {
+        let err = eval_intrinsic(Intrinsic::Sub, &[Const::Bool(true)], &Type::Bool).unwrap_err();
+        assert_eq!(err, EvalError::IntrinsicArityMismatch { intr: Intrinsic::Sub, expected: 2, found: 1 });
+    }
+
+    #[test]
+    fn truncate_masks_to_the_target_width() {
+        let c = Const::Int { lit: 300, ty: Type::I32 };
+        let truncated = truncate(c, &Type::I8).unwrap();
+        assert_eq!(truncated, Const::Int { lit: 44, ty: Type::I8 });
+    }
+
+    #[test]
+    fn sign_extend_replicates_the_high_bit() {
+        let c = Const::Int { lit: 0xff, ty: Type::I8 }; // -1 as i8
+        let extended = sign_extend(c, &Type::I32).unwrap();
+        assert_eq!(extended, Const::Int { lit: 0xffff_ffff, ty: Type::I32 });
+    }
+
+    #[test]
+    fn zero_extend_does_not_replicate_the_high_bit() {
+        let c = Const::Int { lit: 0xff, ty: Type::I8 };
+        let extended = zero_extend(c, &Type::I32).unwrap();
+        assert_eq!(extended, Const::Int { lit: 0xff, ty: Type::I32 });
+    }
+
+    #[test]
+    fn bytes_to_const_decodes_floats_by_width_instead_of_as_int() {
+        let bytes = (1.5f64).to_le_bytes();
+        assert_eq!(bytes_to_const(&bytes, &Type::F64), Const::Float { lit: 1.5, ty: Type::F64 });
+    }
+}
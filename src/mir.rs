@@ -38,10 +38,30 @@ pub enum Instr {
     DirectFieldAccess { val: InstrId, index: usize },
     IndirectFieldAccess { val: InstrId, index: usize },
     Ret(InstrId),
-    Br(BlockId),
-    CondBr { condition: InstrId, true_bb: BlockId, false_bb: BlockId },
-    /// Only valid at the beginning of a function, right after the void instruction
-    Parameter(Type),
+    Br { target: BlockId, args: SmallVec<[InstrId; 2]> },
+    CondBr {
+        condition: InstrId,
+        true_bb: BlockId,
+        true_args: SmallVec<[InstrId; 2]>,
+        false_bb: BlockId,
+        false_args: SmallVec<[InstrId; 2]>,
+    },
+}
+
+/// A value usable as an operand: either the result of an instruction, or a
+/// parameter of the block it's defined in.
+///
+/// Block parameters replace the old run of `Instr::Parameter` instructions:
+/// instead of reconstructing dataflow across edges from a fragile
+/// instruction prefix, every block (including the entry block) declares the
+/// types of the values its predecessors must hand it, and `Br`/`CondBr`
+/// supply matching `args`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Value {
+    /// A value defined by a block parameter.
+    Param { block: BlockId, param_idx: usize },
+    /// A value defined by an instruction.
+    Instr(InstrId),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -73,7 +93,8 @@ impl Const {
 pub struct Function {
     pub name: Option<Sym>,
     pub ret_ty: Type,
-    /// Index 0 is defined to be the entry block
+    /// Index 0 is defined to be the entry block. The entry block's params
+    /// (see `MirCode::block_params`) are this function's parameters, in order.
     pub blocks: Vec<BlockId>,
 }
 
@@ -105,19 +126,12 @@ impl Code {
         block.ops[op].as_mir_instr().map(|instr| &self.mir_code.instrs[instr])
     }
 
+    /// The function's parameters are the entry block's params, so this is
+    /// now a simple field read rather than a scan over a run of
+    /// `Instr::Parameter`s.
     pub fn num_parameters(&self, func: &Function) -> usize {
         let entry = func.blocks[0];
-        let block = &self.blocks[entry];
-        let void_instr = self.get_mir_instr(block, OpId::new(0)).unwrap();
-        assert_eq!(void_instr, &Instr::Void);
-        let mut num_parameters = 0;
-        for i in 1..block.ops.len() {
-            match self.get_mir_instr(block, OpId::new(i)).unwrap() {
-                Instr::Parameter(_) => num_parameters += 1,
-                _ => break,
-            }
-        }
-        num_parameters
+        self.mir_code.block_params(entry).len()
     }
 }
 
@@ -150,6 +164,13 @@ pub struct MirCode {
     pub structs: HashMap<StructId, Struct>,
     pub instrs: IndexVec<InstrId, Instr>,
     block_states: HashMap<BlockId, BlockState>,
+    /// Each block's params, in order, as `(InstrId, Type)` pairs: the
+    /// `InstrId` is the value other instructions reference to read that
+    /// param (it names no entry in `instrs`, since a param isn't produced by
+    /// any instruction), and the `Type` is what a predecessor's `Br`/
+    /// `CondBr` args must match. The entry block's params double as its
+    /// function's parameters (see `num_parameters`).
+    block_params: HashMap<BlockId, SmallVec<[(InstrId, Type); 2]>>,
 }
 
 impl MirCode {
@@ -157,6 +178,32 @@ impl MirCode {
         self.block_states.entry(block).or_insert(BlockState::Created)
     }
 
+    pub fn block_params(&self, block: BlockId) -> &[(InstrId, Type)] {
+        self.block_params.get(&block).map_or(&[], |params| &params[..])
+    }
+
+    /// The block's lifecycle state, or `None` if it hasn't been touched by
+    /// `start_block` yet.
+    pub fn block_state(&self, block: BlockId) -> Option<&BlockState> {
+        self.block_states.get(&block)
+    }
+
+    /// The block's param value ids, in order, for use wherever an operand
+    /// needs to name "the Nth param of this block".
+    pub fn block_param_instrs(&self, block: BlockId) -> impl Iterator<Item = InstrId> + '_ {
+        self.block_params(block).iter().map(|&(id, _)| id)
+    }
+
+    pub fn set_block_params(&mut self, block: BlockId, params: SmallVec<[(InstrId, Type); 2]>) {
+        self.block_params.insert(block, params);
+    }
+
+    /// Whether `instr` names a param of `block` rather than a real entry in
+    /// `instrs`.
+    pub fn is_block_param(&self, block: BlockId, instr: InstrId) -> bool {
+        self.block_params(block).iter().any(|&(id, _)| id == instr)
+    }
+
     pub fn start_block(&mut self, block: BlockId) {
         let state = self.get_block_state(block);
         assert!(!matches!(state, BlockState::Ended), "MIR: tried to start an ended block");
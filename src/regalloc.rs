@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cfg::Cfg;
+use crate::mir::{Function, Instr, InstrId, Value};
+use crate::ty::Type;
+use crate::{BlockId, Code, OpId};
+
+/// The two sub-positions of an instruction within its [`ProgPoint`]
+/// numbering. [`compute_live_intervals`] starts a value's interval at its
+/// definition's `Early` point and ends it at its last use's `Late` point,
+/// so an instruction's operands and its result are live together at that
+/// instruction and never share a register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Early,
+    Late,
+}
+
+/// A program point: an `InstrId` plus an early/late [`Stage`], packed as
+/// `inst << 1 | stage`, in the spirit of the external register allocator's
+/// `ProgPoint`. Cheap to compare and to step by half-instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProgPoint(usize);
+
+impl ProgPoint {
+    pub fn new(inst: InstrId, stage: Stage) -> ProgPoint {
+        let bit = match stage {
+            Stage::Early => 0,
+            Stage::Late => 1,
+        };
+        ProgPoint((inst.index() << 1) | bit)
+    }
+
+    pub fn inst(self) -> InstrId {
+        InstrId::new(self.0 >> 1)
+    }
+
+    pub fn stage(self) -> Stage {
+        if self.0 & 1 == 0 { Stage::Early } else { Stage::Late }
+    }
+
+    pub fn next(self) -> ProgPoint {
+        ProgPoint(self.0 + 1)
+    }
+
+    pub fn prev(self) -> ProgPoint {
+        ProgPoint(self.0 - 1)
+    }
+}
+
+/// A dense numbering of every instruction and block parameter in a
+/// function's CFG layout order (reverse postorder), used to order
+/// [`ProgPoint`]s by position rather than by raw `InstrId` (which reflects
+/// creation order, not layout order, once loops and branches are involved).
+pub struct ProgramOrder {
+    position: HashMap<ProgPoint, usize>,
+}
+
+impl ProgramOrder {
+    pub fn build(code: &Code, cfg: &Cfg) -> ProgramOrder {
+        let mut position = HashMap::new();
+        let mut next = 0usize;
+        for &block_id in cfg.reverse_postorder_blocks() {
+            for &(param, _) in code.mir_code.block_params(block_id) {
+                position.insert(ProgPoint::new(param, Stage::Early), next);
+                position.insert(ProgPoint::new(param, Stage::Late), next + 1);
+                next += 2;
+            }
+            let block = &code.blocks[block_id];
+            for i in 0..block.ops.len() {
+                if let Some(instr_id) = block.ops[OpId::new(i)].as_mir_instr() {
+                    position.insert(ProgPoint::new(instr_id, Stage::Early), next);
+                    position.insert(ProgPoint::new(instr_id, Stage::Late), next + 1);
+                    next += 2;
+                }
+            }
+        }
+        ProgramOrder { position }
+    }
+
+    /// The position of `pp` in layout order, for use as a sort/comparison
+    /// key. Points outside `func` (there shouldn't be any) sort last.
+    fn key(&self, pp: ProgPoint) -> usize {
+        self.position.get(&pp).copied().unwrap_or(usize::MAX)
+    }
+}
+
+/// A value's live range: the half-open `[start, end)` span of program
+/// points over which it must be kept somewhere (a register or a spill
+/// slot). One interval per value, covering every point from its definition
+/// through its last use, including uses reached only through back edges
+/// once dataflow has converged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiveInterval {
+    pub value: Value,
+    pub start: ProgPoint,
+    pub end: ProgPoint,
+}
+
+/// Where in the function a value is defined, plus its result type if
+/// [`Code::instr_result_ty`] can determine one.
+struct DefSite {
+    value: Value,
+    ty: Option<Type>,
+}
+
+/// Walks `block_id`'s instructions in order, yielding each one's `InstrId`.
+fn block_ops(code: &Code, block_id: BlockId) -> impl Iterator<Item = InstrId> + '_ {
+    let block = &code.blocks[block_id];
+    (0..block.ops.len()).filter_map(move |i| block.ops[OpId::new(i)].as_mir_instr())
+}
+
+fn terminator_of(code: &Code, block_id: BlockId) -> Option<InstrId> {
+    block_ops(code, block_id).last()
+}
+
+fn collect(code: &Code, func: &Function) -> HashMap<InstrId, DefSite> {
+    let mut defs = HashMap::new();
+    for &block_id in &func.blocks {
+        for (param_idx, &(param, ref ty)) in code.mir_code.block_params(block_id).iter().enumerate() {
+            defs.insert(param, DefSite { value: Value::Param { block: block_id, param_idx }, ty: Some(ty.clone()) });
+        }
+        for instr_id in block_ops(code, block_id) {
+            let ty = code.instr_at(block_id, instr_id).and_then(|instr| code.instr_result_ty(instr));
+            defs.insert(instr_id, DefSite { value: Value::Instr(instr_id), ty });
+        }
+    }
+    defs
+}
+
+/// For each outgoing edge of `block_id`, the branch args passed to the
+/// successor, paired with the successor's id.
+fn out_edges(code: &Code, block_id: BlockId, term: InstrId) -> Vec<(BlockId, Vec<InstrId>)> {
+    let Some(instr) = code.instr_at(block_id, term) else { return vec![] };
+    match instr {
+        Instr::Br { target, args } => vec![(*target, args.to_vec())],
+        Instr::CondBr { true_bb, true_args, false_bb, false_args, .. } => {
+            vec![(*true_bb, true_args.to_vec()), (*false_bb, false_args.to_vec())]
+        }
+        _ => vec![],
+    }
+}
+
+/// `live_out` of a block is the union, over each successor edge, of that
+/// successor's `live_in` — except a successor's own block params are not
+/// themselves live before the edge; instead, whichever of the edge's args
+/// feeds a live param is live coming out of `block_id`.
+fn live_out_of(code: &Code, block_id: BlockId, term: Option<InstrId>, live_in: &HashMap<BlockId, HashSet<InstrId>>) -> HashSet<InstrId> {
+    let mut out = HashSet::new();
+    let Some(term) = term else { return out };
+    for (succ, args) in out_edges(code, block_id, term) {
+        for &live in &live_in[&succ] {
+            match code.mir_code.block_params(succ).iter().position(|&(id, _)| id == live) {
+                Some(param_idx) => {
+                    out.insert(args[param_idx]);
+                }
+                None => {
+                    out.insert(live);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Precise backward liveness within one block: starting from `live_out`,
+/// walk instructions last-to-first, removing each one's own def (it's no
+/// longer needed before its own definition point) before adding the values
+/// it uses. This is what makes a value that's defined and consumed wholly
+/// inside one block correctly *not* propagate into `live_in`.
+fn live_in_of(code: &Code, block_id: BlockId, live_out: HashSet<InstrId>) -> HashSet<InstrId> {
+    let mut live = live_out;
+    for instr_id in block_ops(code, block_id).collect::<Vec<_>>().into_iter().rev() {
+        live.remove(&instr_id);
+        if let Some(instr) = code.instr_at(block_id, instr_id) {
+            live.extend(Code::operands(instr));
+        }
+    }
+    for &(param, _) in code.mir_code.block_params(block_id) {
+        live.remove(&param);
+    }
+    live
+}
+
+/// Computes one [`LiveInterval`] per value defined in `func`, via the
+/// standard backward dataflow: `live_out` of a block is derived from its
+/// successors' `live_in` (see [`live_out_of`]), and `live_in` is found by
+/// walking the block bottom-up (see [`live_in_of`]). Blocks are revisited
+/// to a fixed point so loop back edges see the right `live_in`. Each
+/// value's interval then spans from its definition to the last point (a
+/// use, or the end of a block it's live out of) touched during that
+/// dataflow.
+pub fn compute_live_intervals(code: &Code, func: &Function, cfg: &Cfg) -> Vec<LiveInterval> {
+    let defs = collect(code, func);
+
+    let mut live_in: HashMap<BlockId, HashSet<InstrId>> = func.blocks.iter().map(|&b| (b, HashSet::new())).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block_id in cfg.reverse_postorder_blocks().iter().rev() {
+            let term = terminator_of(code, block_id);
+            let out = live_out_of(code, block_id, term, &live_in);
+            let new_in = live_in_of(code, block_id, out);
+            if new_in != live_in[&block_id] {
+                live_in.insert(block_id, new_in);
+                changed = true;
+            }
+        }
+    }
+
+    let order = ProgramOrder::build(code, cfg);
+    let mut end: HashMap<InstrId, ProgPoint> = HashMap::new();
+    let mut extend = |id: InstrId, pp: ProgPoint, end: &mut HashMap<InstrId, ProgPoint>| match end.get(&id) {
+        Some(&cur) if order.key(cur) >= order.key(pp) => {}
+        _ => {
+            end.insert(id, pp);
+        }
+    };
+
+    for &block_id in &func.blocks {
+        let Some(term) = terminator_of(code, block_id) else { continue };
+        for instr_id in block_ops(code, block_id) {
+            let Some(instr) = code.instr_at(block_id, instr_id) else { continue };
+            for used in Code::operands(instr) {
+                extend(used, ProgPoint::new(instr_id, Stage::Late), &mut end);
+            }
+        }
+        for &value in &live_out_of(code, block_id, Some(term), &live_in) {
+            extend(value, ProgPoint::new(term, Stage::Late), &mut end);
+        }
+    }
+
+    defs.into_iter()
+        .map(|(instr_id, site)| {
+            let start = ProgPoint::new(instr_id, Stage::Early);
+            let end = end.get(&instr_id).copied().filter(|&e| order.key(e) >= order.key(start)).unwrap_or(start);
+            LiveInterval { value: site.value, start, end }
+        })
+        .collect()
+}
+
+/// Where linear scan decided a value should live.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assignment {
+    Register(usize),
+    Spill(usize),
+}
+
+/// The concrete instructions needed to materialize a spilled value: an
+/// `Alloca` for its slot (hoisted to the function entry), a `Store` right
+/// after its definition, and a `Load` before each use. Only produced when
+/// the value's type is statically known (see `Code::instr_result_ty`) —
+/// values whose type isn't tracked by this snapshot (e.g. the result of a
+/// `Load` or `Call`) still get a spill slot in `RegAlloc::assignments`,
+/// but codegen must supply its own `Store`/`Load` for them.
+///
+/// `alloca` has not been inserted anywhere yet, so there's no `InstrId` to
+/// put in the `Store`'s/`Load`s' `location` operand until codegen hoists it
+/// to the entry block and learns the `InstrId` it landed at. `store_after_def`
+/// and `loads_before_use` take that `InstrId` (call it `slot_instr`) and
+/// build the real instruction once it's known.
+pub struct SpillCode {
+    pub value: Value,
+    pub slot: usize,
+    pub alloca: Instr,
+    def_instr: InstrId,
+    use_sites: Vec<InstrId>,
+}
+
+impl SpillCode {
+    /// The `Store` to insert right after `def_instr`, once `alloca` has
+    /// been hoisted to the entry block under `slot_instr`.
+    pub fn store_after_def(&self, slot_instr: InstrId) -> Instr {
+        Instr::Store { location: slot_instr, value: self.def_instr }
+    }
+
+    /// The `Load` to insert before each use in `use_sites`, paired with the
+    /// `InstrId` of the use it precedes, once `slot_instr` is known.
+    pub fn loads_before_use(&self, slot_instr: InstrId) -> Vec<(InstrId, Instr)> {
+        self.use_sites.iter().map(|&use_site| (use_site, Instr::Load(slot_instr))).collect()
+    }
+}
+
+pub struct RegAlloc {
+    pub assignments: HashMap<Value, Assignment>,
+    pub spills: Vec<SpillCode>,
+}
+
+/// Linear-scan register allocation (Poletto & Sarkar) over the intervals
+/// from [`compute_live_intervals`]: intervals are processed in start
+/// order, tracking an `active` set of currently-live register assignments;
+/// when the fixed register pool is exhausted, whichever of the active
+/// intervals and the incoming one ends furthest away is spilled (the
+/// incoming one on a tie), since it has the most to gain from living in
+/// memory rather than blocking a nearer-term value.
+pub fn allocate(code: &Code, func: &Function, cfg: &Cfg, num_registers: usize) -> RegAlloc {
+    let order = ProgramOrder::build(code, cfg);
+    let defs = collect(code, func);
+
+    let mut intervals = compute_live_intervals(code, func, cfg);
+    intervals.sort_by_key(|iv| order.key(iv.start));
+
+    let mut free_registers: Vec<usize> = (0..num_registers).rev().collect();
+    let mut active: Vec<(LiveInterval, usize)> = Vec::new();
+    let mut assignments = HashMap::new();
+    let mut next_spill_slot = 0usize;
+
+    for interval in intervals {
+        active.retain(|&(other, reg)| {
+            if order.key(other.end) <= order.key(interval.start) {
+                free_registers.push(reg);
+                false
+            } else {
+                true
+            }
+        });
+
+        if active.len() >= num_registers {
+            // With zero registers `active` is always empty (nothing to spill
+            // in its place), so the incoming interval itself is the only
+            // candidate.
+            let furthest = active.iter().enumerate().max_by_key(|(_, (iv, _))| order.key(iv.end)).map(|(i, _)| i);
+            let should_spill_active = furthest.is_some_and(|i| order.key(active[i].0.end) > order.key(interval.end));
+            if should_spill_active {
+                let i = furthest.expect("should_spill_active implies furthest is Some");
+                let (spill_candidate, candidate_reg) = active[i];
+                assignments.insert(spill_candidate.value, Assignment::Spill(next_spill_slot));
+                next_spill_slot += 1;
+                active[i] = (interval, candidate_reg);
+                assignments.insert(interval.value, Assignment::Register(candidate_reg));
+            } else {
+                assignments.insert(interval.value, Assignment::Spill(next_spill_slot));
+                next_spill_slot += 1;
+            }
+            continue;
+        }
+
+        let reg = free_registers.pop().expect("active.len() < num_registers implies a free register");
+        assignments.insert(interval.value, Assignment::Register(reg));
+        active.push((interval, reg));
+    }
+
+    let mut uses_by_value: HashMap<Value, Vec<InstrId>> = HashMap::new();
+    for &block_id in &func.blocks {
+        for instr_id in block_ops(code, block_id) {
+            let Some(instr) = code.instr_at(block_id, instr_id) else { continue };
+            for used in Code::operands(instr) {
+                if let Some(site) = defs.get(&used) {
+                    uses_by_value.entry(site.value).or_default().push(instr_id);
+                }
+            }
+        }
+    }
+
+    let mut spills = Vec::new();
+    for (&value, assignment) in &assignments {
+        let Assignment::Spill(slot) = *assignment else { continue };
+        let def_instr = match value {
+            Value::Param { block, param_idx } => code.mir_code.block_params(block)[param_idx].0,
+            Value::Instr(id) => id,
+        };
+        let Some(ty) = defs.get(&def_instr).and_then(|site| site.ty.clone()) else { continue };
+        spills.push(SpillCode {
+            value,
+            slot,
+            alloca: Instr::Alloca(ty),
+            def_instr,
+            use_sites: uses_by_value.get(&value).into_iter().flatten().copied().collect(),
+        });
+    }
+
+    RegAlloc { assignments, spills }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prog_point_orders_early_before_late_at_the_same_instr() {
+        let inst = InstrId::new(3);
+        let early = ProgPoint::new(inst, Stage::Early);
+        let late = ProgPoint::new(inst, Stage::Late);
+        assert!(early < late);
+        assert_eq!(early.next(), late);
+        assert_eq!(late.prev(), early);
+        assert_eq!(early.inst(), inst);
+        assert_eq!(late.stage(), Stage::Late);
+    }
+
+    #[test]
+    fn prog_point_orders_by_instr_before_stage() {
+        let a = ProgPoint::new(InstrId::new(1), Stage::Late);
+        let b = ProgPoint::new(InstrId::new(2), Stage::Early);
+        assert!(a < b);
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::cfg::Cfg;
+use crate::mir::{Function, Instr, InstrId, Value};
+use crate::ty::Type;
+use crate::{BlockId, Code, OpId};
+
+/// Errors produced by [`Code::verify`]. Each variant carries enough of the
+/// offending location (a `BlockId` and/or `InstrId`) for a front-end to
+/// point at the right place without re-walking the function.
+#[derive(Debug, Error, PartialEq)]
+pub enum VerifyError {
+    #[error("function has no entry block")]
+    NoEntryBlock,
+    #[error("entry block {0:?} is the target of a branch, but nothing may branch to the entry: its params come from the function's own arguments")]
+    EntryBlockHasPredecessors(BlockId),
+    #[error("block {0:?} does not end in a terminator")]
+    UnterminatedBlock(BlockId),
+    #[error("terminator {1:?} appears before the end of block {0:?}")]
+    TerminatorMidBlock(BlockId, InstrId),
+    #[error("block {0:?} branches to {1:?}, which is not one of its function's blocks")]
+    UnknownBranchTarget(BlockId, BlockId),
+    #[error("block {from:?} branches to {to:?} with {found} argument(s), but {to:?} declares {expected} parameter(s)")]
+    BranchArgCountMismatch { from: BlockId, to: BlockId, expected: usize, found: usize },
+    #[error("block {from:?} branches to {to:?} passing a {found:?} for parameter {index}, but it expects {expected:?}")]
+    BranchArgTypeMismatch { from: BlockId, to: BlockId, index: usize, expected: Type, found: Type },
+    #[error("{value:?} is used in block {used_in:?} but its definition does not dominate that use")]
+    UseNotDominated { value: Value, used_in: BlockId },
+    #[error("store to {location:?} expects a {expected:?}, but the stored value {value:?} is a {found:?}")]
+    StoreTypeMismatch { location: InstrId, value: InstrId, expected: Type, found: Type },
+    #[error("{instr:?} loads from {operand:?}, which is not a pointer-producing instruction")]
+    LoadOperandNotPointer { instr: InstrId, operand: InstrId },
+    #[error("{instr:?} expects {expected} operand, but {operand:?} is a {found:?}")]
+    CastOperandTypeMismatch { instr: InstrId, operand: InstrId, expected: &'static str, found: Type },
+}
+
+/// Whether `ty` is one of the fixed-width integer types, as opposed to a
+/// float, `Bool`, or anything else. Duplicated from `interp.rs`'s
+/// `int_bit_width` rather than shared, since `verify.rs` only needs the
+/// yes/no answer and shouldn't depend on the interpreter's internals.
+fn is_int_ty(ty: &Type) -> bool {
+    matches!(ty, Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::U8 | Type::U16 | Type::U32 | Type::U64)
+}
+
+fn is_float_ty(ty: &Type) -> bool {
+    matches!(ty, Type::F32 | Type::F64)
+}
+
+/// Where a value is defined, as found by `Code::index_defs`.
+struct DefSite {
+    block: BlockId,
+    /// Position within `block`'s ops, for a regular instruction. `None` for
+    /// a block param, which is defined before every op in the block.
+    op_index: Option<usize>,
+    /// This value's index among its block's params, if it is one.
+    param_idx: Option<usize>,
+}
+
+impl Code {
+    /// Runs full structural validation over `func`, turning what used to be
+    /// scattered `assert!`s (see `BlockState`/`check_all_blocks_ended`) into
+    /// a single recoverable checking pass.
+    pub fn verify(&self, func: &Function) -> Result<(), VerifyError> {
+        if func.blocks.is_empty() {
+            return Err(VerifyError::NoEntryBlock);
+        }
+        let entry = func.blocks[0];
+        let cfg = Cfg::build(self, func);
+        if !cfg.predecessors(entry).is_empty() {
+            return Err(VerifyError::EntryBlockHasPredecessors(entry));
+        }
+
+        self.verify_terminators(func)?;
+        let defs = self.index_defs(func);
+        self.verify_branch_args(func, &defs)?;
+        self.verify_dominance(func, &defs, &cfg)?;
+        self.verify_store_types(func, &defs)?;
+        self.verify_operand_types(func, &defs)
+    }
+
+    /// Indexes where every value in `func` is defined: a block param is
+    /// defined at the top of its block (`op_index: None`, since it names no
+    /// entry in `instrs`), a regular instruction at its `op_index` within
+    /// its block. Built once per `verify` call and threaded through the
+    /// passes below, which all need to resolve an `InstrId` back to its
+    /// definition site.
+    fn index_defs(&self, func: &Function) -> HashMap<InstrId, DefSite> {
+        let mut map = HashMap::new();
+        for &block_id in &func.blocks {
+            for (param_idx, &(param, _)) in self.mir_code.block_params(block_id).iter().enumerate() {
+                map.insert(param, DefSite { block: block_id, op_index: None, param_idx: Some(param_idx) });
+            }
+            let block = &self.blocks[block_id];
+            for i in 0..block.ops.len() {
+                if let Some(instr) = block.ops.get(OpId::new(i)).and_then(|op| op.as_mir_instr()) {
+                    map.insert(instr, DefSite { block: block_id, op_index: Some(i), param_idx: None });
+                }
+            }
+        }
+        map
+    }
+
+    /// The `Value` and result `Type` (if known) of `id`, given where
+    /// `index_defs` says it's defined.
+    fn resolve(&self, id: InstrId, site: &DefSite) -> (Value, Option<Type>) {
+        match site.param_idx {
+            Some(param_idx) => {
+                let ty = self.mir_code.block_params(site.block)[param_idx].1.clone();
+                (Value::Param { block: site.block, param_idx }, Some(ty))
+            }
+            None => {
+                let ty = self.instr_at(site.block, id).and_then(|instr| self.instr_result_ty(instr));
+                (Value::Instr(id), ty)
+            }
+        }
+    }
+
+    fn terminator(&self, block_id: BlockId) -> Option<&Instr> {
+        let block = &self.blocks[block_id];
+        if block.ops.len() == 0 {
+            return None;
+        }
+        self.get_mir_instr(block_id, OpId::new(block.ops.len() - 1))
+    }
+
+    fn is_terminator(instr: &Instr) -> bool {
+        matches!(instr, Instr::Ret(_) | Instr::Br { .. } | Instr::CondBr { .. })
+    }
+
+    fn verify_terminators(&self, func: &Function) -> Result<(), VerifyError> {
+        for &block_id in &func.blocks {
+            let block = &self.blocks[block_id];
+            let mut terminator: Option<InstrId> = None;
+            for i in 0..block.ops.len() {
+                let Some(instr) = self.get_mir_instr(block_id, OpId::new(i)) else { continue };
+                if let Some(term_id) = terminator {
+                    return Err(VerifyError::TerminatorMidBlock(block_id, term_id));
+                }
+                if Self::is_terminator(instr) {
+                    terminator = block.ops[OpId::new(i)].as_mir_instr();
+                }
+            }
+            if terminator.is_none() {
+                return Err(VerifyError::UnterminatedBlock(block_id));
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_branch_args(&self, func: &Function, defs: &HashMap<InstrId, DefSite>) -> Result<(), VerifyError> {
+        let known: HashSet<BlockId> = func.blocks.iter().copied().collect();
+        let check_target = |from: BlockId, to: BlockId, args: &[InstrId]| -> Result<(), VerifyError> {
+            if !known.contains(&to) {
+                return Err(VerifyError::UnknownBranchTarget(from, to));
+            }
+            let params = self.mir_code.block_params(to);
+            if params.len() != args.len() {
+                return Err(VerifyError::BranchArgCountMismatch {
+                    from,
+                    to,
+                    expected: params.len(),
+                    found: args.len(),
+                });
+            }
+            for (index, &(_, ref expected)) in params.iter().enumerate() {
+                let arg = args[index];
+                let Some(site) = defs.get(&arg) else { continue };
+                let (_, found) = self.resolve(arg, site);
+                let Some(found) = found else { continue };
+                if found != *expected {
+                    return Err(VerifyError::BranchArgTypeMismatch { from, to, index, expected: expected.clone(), found });
+                }
+            }
+            Ok(())
+        };
+        for &block_id in &func.blocks {
+            if let Some(term) = self.terminator(block_id) {
+                match term {
+                    Instr::Br { target, args } => check_target(block_id, *target, args)?,
+                    Instr::CondBr { true_bb, true_args, false_bb, false_args, .. } => {
+                        check_target(block_id, *true_bb, true_args)?;
+                        check_target(block_id, *false_bb, false_args)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_dominance(&self, func: &Function, defs: &HashMap<InstrId, DefSite>, cfg: &Cfg) -> Result<(), VerifyError> {
+        for &block_id in &func.blocks {
+            let block = &self.blocks[block_id];
+            for i in 0..block.ops.len() {
+                let Some(instr) = self.get_mir_instr(block_id, OpId::new(i)) else { continue };
+                for used in Self::operands(instr) {
+                    let Some(site) = defs.get(&used) else { continue };
+                    let dominates = if site.block != block_id {
+                        cfg.dominates(site.block, block_id)
+                    } else {
+                        // Same block: a param dominates every op (it's
+                        // defined before all of them), but a regular
+                        // instruction must appear strictly before this use's
+                        // op index, or it's a forward reference.
+                        match site.op_index {
+                            Some(def_i) => def_i < i,
+                            None => true,
+                        }
+                    };
+                    if !dominates {
+                        let (value, _) = self.resolve(used, site);
+                        return Err(VerifyError::UseNotDominated { value, used_in: block_id });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn operands(instr: &Instr) -> Vec<InstrId> {
+        match instr {
+            Instr::LogicalNot(a)
+            | Instr::Reinterpret(a, _)
+            | Instr::Truncate(a, _)
+            | Instr::SignExtend(a, _)
+            | Instr::ZeroExtend(a, _)
+            | Instr::FloatCast(a, _)
+            | Instr::FloatToInt(a, _)
+            | Instr::IntToFloat(a, _)
+            | Instr::Load(a)
+            | Instr::Ret(a)
+            | Instr::DirectFieldAccess { val: a, .. }
+            | Instr::IndirectFieldAccess { val: a, .. }
+            | Instr::Pointer { op: a, .. } => vec![*a],
+            Instr::Store { location, value } => vec![*location, *value],
+            Instr::Call { arguments, .. } | Instr::Intrinsic { arguments, .. } => arguments.to_vec(),
+            Instr::Struct { fields, .. } | Instr::StructLit { fields, .. } => fields.to_vec(),
+            Instr::CondBr { condition, true_args, false_args, .. } => {
+                let mut ops = vec![*condition];
+                ops.extend(true_args.iter().copied());
+                ops.extend(false_args.iter().copied());
+                ops
+            }
+            Instr::Br { args, .. } => args.to_vec(),
+            Instr::Void | Instr::Const(_) | Instr::Alloca(_) | Instr::AddressOfStatic(_) => vec![],
+        }
+    }
+
+    pub(crate) fn instr_result_ty(&self, instr: &Instr) -> Option<Type> {
+        match instr {
+            Instr::Const(c) => Some(c.ty()),
+            Instr::Alloca(ty)
+            | Instr::Reinterpret(_, ty)
+            | Instr::Truncate(_, ty)
+            | Instr::SignExtend(_, ty)
+            | Instr::ZeroExtend(_, ty)
+            | Instr::FloatCast(_, ty)
+            | Instr::FloatToInt(_, ty)
+            | Instr::IntToFloat(_, ty) => Some(ty.clone()),
+            Instr::Intrinsic { ty, .. } => Some(ty.clone()),
+            &Instr::Struct { id, .. } | &Instr::StructLit { id, .. } => Some(Type::Struct(id)),
+            _ => None,
+        }
+    }
+
+    /// Checks that a `Store`'s value matches the type the location was
+    /// `Alloca`'d with. This only covers the directly-resolvable case (the
+    /// location instruction is itself an `Alloca`); deeper pointer aliasing
+    /// is left to the const-evaluator's allocation model.
+    fn verify_store_types(&self, func: &Function, defs: &HashMap<InstrId, DefSite>) -> Result<(), VerifyError> {
+        for &block_id in &func.blocks {
+            let block = &self.blocks[block_id];
+            for i in 0..block.ops.len() {
+                let Some(Instr::Store { location, value }) = self.get_mir_instr(block_id, OpId::new(i)) else { continue };
+                let Some(loc_site) = defs.get(location) else { continue };
+                let Some(Instr::Alloca(expected)) = self.instr_at(loc_site.block, *location) else { continue };
+                let expected = expected.clone();
+                let Some(val_site) = defs.get(value) else { continue };
+                let (_, found) = self.resolve(*value, val_site);
+                let Some(found) = found else { continue };
+                if expected != found {
+                    return Err(VerifyError::StoreTypeMismatch { location: *location, value: *value, expected, found });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the operand types of `Load` and the cast family
+    /// (`Truncate`/`SignExtend`/`ZeroExtend`/`Reinterpret`/`FloatCast`/
+    /// `FloatToInt`/`IntToFloat`). A cast operand is checked against its
+    /// declared type, including a block param's; an operand whose type
+    /// `instr_result_ty` can't determine (e.g. the result of a `Load` or
+    /// `Call`), or that isn't defined in `func`, is passed permissively.
+    fn verify_operand_types(&self, func: &Function, defs: &HashMap<InstrId, DefSite>) -> Result<(), VerifyError> {
+        for &block_id in &func.blocks {
+            let block = &self.blocks[block_id];
+            for i in 0..block.ops.len() {
+                let Some(instr) = self.get_mir_instr(block_id, OpId::new(i)) else { continue };
+                let Some(instr_id) = block.ops[OpId::new(i)].as_mir_instr() else { continue };
+                match instr {
+                    Instr::Load(ptr) => self.check_load_operand(instr_id, *ptr, defs)?,
+                    Instr::Truncate(v, _) | Instr::SignExtend(v, _) | Instr::ZeroExtend(v, _) | Instr::Reinterpret(v, _) => {
+                        self.check_operand_category(instr_id, *v, defs, is_int_ty, "an integer")?
+                    }
+                    Instr::IntToFloat(v, _) => self.check_operand_category(instr_id, *v, defs, is_int_ty, "an integer")?,
+                    Instr::FloatToInt(v, _) | Instr::FloatCast(v, _) => {
+                        self.check_operand_category(instr_id, *v, defs, is_float_ty, "a float")?
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `Load`'s operand doesn't carry a `Type` to check against (it names no
+    /// pointee type of its own), so instead of a type comparison this checks
+    /// that it's produced by one of the instructions that actually yield a
+    /// pointer. A block param is passed permissively, like a `Store`'s
+    /// location in `verify_store_types`: its producing instruction isn't
+    /// visible here.
+    fn check_load_operand(&self, instr: InstrId, ptr: InstrId, defs: &HashMap<InstrId, DefSite>) -> Result<(), VerifyError> {
+        let Some(site) = defs.get(&ptr) else { return Ok(()) };
+        if site.param_idx.is_some() {
+            return Ok(());
+        }
+        let Some(producer) = self.instr_at(site.block, ptr) else { return Ok(()) };
+        let is_pointer_producer =
+            matches!(producer, Instr::Alloca(_) | Instr::AddressOfStatic(_) | Instr::Pointer { .. } | Instr::Load(_));
+        if !is_pointer_producer {
+            return Err(VerifyError::LoadOperandNotPointer { instr, operand: ptr });
+        }
+        Ok(())
+    }
+
+    fn check_operand_category(
+        &self,
+        instr: InstrId,
+        operand: InstrId,
+        defs: &HashMap<InstrId, DefSite>,
+        matches_category: fn(&Type) -> bool,
+        expected: &'static str,
+    ) -> Result<(), VerifyError> {
+        let Some(site) = defs.get(&operand) else { return Ok(()) };
+        let (_, found) = self.resolve(operand, site);
+        let Some(found) = found else { return Ok(()) };
+        if !matches_category(&found) {
+            return Err(VerifyError::CastOperandTypeMismatch { instr, operand, expected, found });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn instr_at(&self, block_id: BlockId, target: InstrId) -> Option<&Instr> {
+        let block = &self.blocks[block_id];
+        for i in 0..block.ops.len() {
+            if let Some(op) = block.ops.get(OpId::new(i)) {
+                if op.as_mir_instr() == Some(target) {
+                    return self.get_mir_instr(block_id, OpId::new(i));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_int_ty_accepts_every_fixed_width_integer() {
+        for ty in [Type::I8, Type::I16, Type::I32, Type::I64, Type::U8, Type::U16, Type::U32, Type::U64] {
+            assert!(is_int_ty(&ty), "{ty:?} should be an integer type");
+        }
+        assert!(!is_int_ty(&Type::F32));
+        assert!(!is_int_ty(&Type::Bool));
+    }
+
+    #[test]
+    fn is_float_ty_accepts_only_the_float_types() {
+        assert!(is_float_ty(&Type::F32));
+        assert!(is_float_ty(&Type::F64));
+        assert!(!is_float_ty(&Type::I32));
+        assert!(!is_float_ty(&Type::Bool));
+    }
+}